@@ -1,16 +1,21 @@
-use std::time::Duration;
-use std::{str::FromStr, sync::Arc};
-
 use alloy_primitives::{address, Address};
 use alloy_sol_types::sol;
-use alloy_sol_types::SolCall;
 use clap::Parser;
-use ethers::{prelude::*, providers::Provider, utils::parse_units};
-use eyre::{bail, eyre, Context, ErrReport, Result};
+use ethers::prelude::*;
+use ethers::providers::Quorum;
+use eyre::{bail, Context, Result};
 use lazy_static::lazy_static;
-use serde_json::Value;
-use spoof::State;
-use transaction::eip2718::TypedTransaction;
+
+mod batch;
+mod middleware;
+mod provider;
+mod receipt;
+mod signer;
+mod tx;
+
+use batch::{batch_activate, load_addresses, ActivationOutcome};
+use middleware::build_client;
+use receipt::write_receipts;
 
 pub const ARB_WASM_ADDRESS: Address = address!("0000000000000000000000000000000000000071");
 
@@ -25,6 +30,7 @@ sol! {
             external
             payable
             returns (uint16 version, uint256 dataFee);
+        function codehashVersion(bytes32 codehash) external view returns (uint16 version);
     }
 }
 
@@ -34,14 +40,60 @@ sol! {
 #[command(propagate_version = true)]
 #[command(version)]
 pub struct CommonConfig {
+    #[arg(long, required_unless_present_any = ["keystore", "ledger"])]
+    private_key: Option<String>,
+    /// Path to an encrypted JSON keystore file. The password is prompted for on the terminal.
+    #[arg(long, conflicts_with_all = ["private_key", "ledger"])]
+    keystore: Option<String>,
+    /// Sign using a Ledger hardware wallet connected over USB.
+    #[arg(long, conflicts_with_all = ["private_key", "keystore"])]
+    ledger: bool,
+    /// BIP-32 derivation path used to select the signing account on the Ledger device.
+    #[arg(long, default_value = "m/44'/60'/0'/0/0")]
+    hd_path: String,
+    /// RPC endpoint to send requests to. May be repeated to build a quorum of
+    /// providers that must agree on a response.
+    #[arg(long, required = true)]
+    endpoint: Vec<String>,
+    /// Number of retries for a rate-limited or otherwise failed RPC request,
+    /// applied per endpoint.
+    #[arg(long, default_value_t = 10)]
+    max_retries: u32,
+    /// Agreement required across `--endpoint`s before a response is accepted:
+    /// `all`, `majority`, or a minimum number of agreeing endpoints.
+    #[arg(long, default_value = "majority", value_parser = provider::parse_quorum)]
+    quorum: Quorum,
+    /// Initial backoff, in milliseconds, before the first retry of a failed RPC request.
+    #[arg(long, default_value_t = 1_000)]
+    initial_backoff_ms: u64,
+    /// Address of a program to activate. May be repeated to activate several
+    /// programs in one run.
     #[arg(long)]
-    private_key: String,
+    address: Vec<H160>,
+    /// Path to a file with one program address per line, merged with `--address`.
     #[arg(long)]
-    endpoint: String,
+    addresses_file: Option<String>,
+    /// Maximum number of activations to have in flight at once.
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    /// Percentage markup applied to the estimated activation data fee (paid as
+    /// `tx.value`). Orthogonal to the gas fee paid for the transaction itself.
     #[arg(long)]
-    address: H160,
+    data_fee_bump_percent: Option<u64>,
+    /// Source `maxFeePerGas`/`maxPriorityFeePerGas` from `eth_feeHistory` instead of
+    /// letting the node estimate them.
     #[arg(long)]
-    bump_fee_percent: Option<u64>,
+    gas_oracle: bool,
+    /// Percentile of recent priority fees (0-100) to target when `--gas-oracle`
+    /// is set; higher values land transactions more reliably during congestion.
+    #[arg(long, default_value_t = 50.0)]
+    priority_fee_percentile: f64,
+    /// Force a legacy (non-EIP-1559) transaction, for chains without a fee market.
+    #[arg(long)]
+    legacy: bool,
+    /// Path to write a signed, machine-readable JSON receipt for each activation.
+    #[arg(long)]
+    receipt_out: Option<String>,
 }
 
 #[tokio::main]
@@ -50,115 +102,30 @@ async fn main() -> Result<()> {
     activate_stylus_program(&cfg).await
 }
 
-/// Activates a Stylus program at a specified address by estimating its activation
-/// data fee from the ArbOS precompile. Then, it sends a tx to activate the program
-/// with a desired bump percentage on the estimated data fee.
+/// Activates one or more Stylus programs by estimating each one's activation
+/// data fee from the ArbOS precompile, then sending a tx to activate it with
+/// a desired bump percentage on the estimated data fee. Programs whose
+/// codehash is already activated are skipped.
 pub async fn activate_stylus_program(cfg: &CommonConfig) -> Result<()> {
-    let provider = Arc::new(new_provider(&cfg.endpoint)?);
-    let chain_id = provider.get_chainid().await?.as_u64();
-    let wallet = LocalWallet::from_str(&cfg.private_key)?;
-    let signer = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(chain_id));
+    let addresses = load_addresses(cfg)?;
+    let (client, chain_id) = build_client(cfg).await?;
+    let reports = batch_activate(cfg, client.clone(), chain_id, addresses).await?;
 
-    let mut data_fee = estimate_activation_data_fee(cfg.address, &signer.provider())
-        .await
-        .wrap_err("failed to check activation via spoofed eth_call")?;
-    println!("Obtained estimated activation data fee {} wei", data_fee);
-    if let Some(bump_percent) = cfg.bump_fee_percent {
-        println!("Bumping estimated activation data fee by {}%", bump_percent);
-        data_fee = bump_data_fee(data_fee, bump_percent);
+    for report in &reports {
+        println!("{report}");
     }
 
-    let program: Address = cfg.address.to_fixed_bytes().into();
-    let data = ArbWasm::activateProgramCall { program }.abi_encode();
-    let tx = Eip1559TransactionRequest::new()
-        .from(signer.address())
-        .to(*ARB_WASM_H160)
-        .value(data_fee)
-        .data(data);
-    let tx = TypedTransaction::Eip1559(tx);
-    let tx = signer.send_transaction(tx, None).await?;
-    match tx.await? {
-        Some(receipt) => {
-            println!(
-                "Successfully activated program {} with tx {}",
-                cfg.address,
-                hex::encode(receipt.transaction_hash),
-            );
-            println!("Receipt: {:?}", receipt);
-        }
-        None => {
-            bail!("Failed to activate program {}", cfg.address);
-        }
-    }
-    Ok(())
-}
-
-async fn estimate_activation_data_fee(address: H160, provider: &Provider<Http>) -> Result<U256> {
-    let program = Address::from(address.to_fixed_bytes());
-    let data = ArbWasm::activateProgramCall { program }.abi_encode();
-    let tx = Eip1559TransactionRequest::new()
-        .to(*ARB_WASM_H160)
-        .data(data)
-        .value(parse_units("1", "ether")?);
-    let code = provider.get_code(address, None).await?;
-    let state: spoof::State = spoof::code(address, code);
-    let outs = funded_eth_call(tx, state, provider).await??;
-    let ArbWasm::activateProgramReturn { dataFee, .. } =
-        ArbWasm::activateProgramCall::abi_decode_returns(&outs, true)?;
-
-    Ok(ethers::types::U256::from_little_endian(
-        dataFee.as_le_slice(),
-    ))
-}
-
-struct EthCallError {
-    #[allow(dead_code)]
-    pub data: Vec<u8>,
-    pub msg: String,
-}
-
-impl From<EthCallError> for ErrReport {
-    fn from(value: EthCallError) -> Self {
-        eyre!(value.msg)
+    if let Some(path) = &cfg.receipt_out {
+        write_receipts(path, &reports, &client)
+            .await
+            .wrap_err("failed to write activation receipts")?;
     }
-}
-
-async fn funded_eth_call(
-    tx: Eip1559TransactionRequest,
-    mut state: State,
-    provider: &Provider<Http>,
-) -> Result<Result<Vec<u8>, EthCallError>> {
-    let tx = TypedTransaction::Eip1559(tx);
-    state.account(Default::default()).balance = Some(ethers::types::U256::MAX); // infinite balance
-
-    match provider.call_raw(&tx).state(&state).await {
-        Ok(bytes) => Ok(Ok(bytes.to_vec())),
-        Err(ProviderError::JsonRpcClientError(error)) => {
-            let error = error
-                .as_error_response()
-                .ok_or_else(|| eyre!("json RPC failure: {error}"))?;
 
-            let msg = error.message.clone();
-            let data = match &error.data {
-                Some(Value::String(data)) => {
-                    hex::decode(data.strip_prefix("0x").unwrap_or(data))?.to_vec()
-                }
-                Some(value) => bail!("failed to decode RPC failure: {value}"),
-                None => vec![],
-            };
-            Ok(Err(EthCallError { data, msg }))
-        }
-        Err(error) => Err(error.into()),
+    if reports
+        .iter()
+        .any(|report| matches!(report.outcome, ActivationOutcome::Failed(_)))
+    {
+        bail!("one or more programs failed to activate");
     }
-}
-
-fn new_provider(url: &str) -> Result<Provider<Http>> {
-    let mut provider = Provider::<Http>::try_from(url).wrap_err("failed to init http provider")?;
-    provider.set_interval(Duration::from_millis(250));
-    Ok(provider)
-}
-
-fn bump_data_fee(fee: U256, pct: u64) -> U256 {
-    let num = 100 + pct;
-    fee * U256::from(num) / U256::from(100)
+    Ok(())
 }
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::middleware::gas_oracle::{
+    GasOracle, GasOracleError, GasOracleMiddleware, ProviderOracle,
+};
+use ethers::middleware::NonceManagerMiddleware;
+use ethers::prelude::*;
+use ethers::providers::Provider;
+use eyre::{Context, Result};
+
+use crate::provider::{new_provider, RpcProvider};
+use crate::signer::SignerBackend;
+use crate::CommonConfig;
+
+/// The middleware stack shared by the data-fee estimation and activation
+/// send paths: nonce management so concurrent activations don't race over
+/// the pending nonce, and gas estimation driven either by the node's
+/// defaults or by our own `eth_feeHistory` oracle.
+///
+/// `NonceManagerMiddleware` must be the outermost layer: `SignerMiddleware`
+/// resolves a missing nonce via the generic `Middleware::get_transaction_count`
+/// default (not overridden by the nonce manager) and sends via
+/// `send_raw_transaction` rather than `send_transaction` (also not
+/// overridden), so a nonce manager sitting underneath it is never actually
+/// consulted and every call would round-trip to the node for its nonce
+/// independently, racing concurrent activations.
+///
+/// The base provider is shared via `Arc` rather than cloned: `RetryClient`
+/// (and so `RpcProvider`/`Provider<RpcProvider>`) isn't `Clone`, but
+/// `Arc<Provider<RpcProvider>>` picks up `Middleware` through ethers' blanket
+/// `Arc<M>` impl, so it can still back both the signer and the gas oracle.
+pub type ActivationClient = NonceManagerMiddleware<
+    GasOracleMiddleware<SignerMiddleware<Arc<Provider<RpcProvider>>, SignerBackend>, OracleChoice>,
+>;
+
+/// Builds the [`ActivationClient`] used for both `estimate_activation_data_fee`
+/// and the activation transaction itself, so the two paths never drift apart.
+/// The chain ID is resolved here, once per run, and returned alongside the
+/// client so callers don't re-resolve it (and re-issue `eth_chainId` through
+/// the quorum/retry stack) per activation.
+pub async fn build_client(cfg: &CommonConfig) -> Result<(Arc<ActivationClient>, u64)> {
+    let provider = Arc::new(new_provider(
+        &cfg.endpoint,
+        cfg.max_retries,
+        cfg.initial_backoff_ms,
+        cfg.quorum,
+    )?);
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let backend = SignerBackend::from_config(
+        &cfg.private_key,
+        &cfg.keystore,
+        cfg.ledger,
+        &cfg.hd_path,
+        chain_id,
+    )
+    .await
+    .wrap_err("failed to set up signer")?;
+    let address = backend.address();
+
+    let signer = SignerMiddleware::new(Arc::clone(&provider), backend);
+
+    let oracle = if cfg.gas_oracle {
+        OracleChoice::FeeHistory(FeeHistoryOracle::new(
+            Arc::clone(&provider),
+            cfg.priority_fee_percentile,
+        ))
+    } else {
+        OracleChoice::Default(ProviderOracle::new(provider))
+    };
+    let gas_oracle = GasOracleMiddleware::new(signer, oracle);
+    let client = NonceManagerMiddleware::new(gas_oracle, address);
+    Ok((Arc::new(client), chain_id))
+}
+
+/// Picks between the node's own gas estimation and our `eth_feeHistory`
+/// oracle at runtime, without changing the concrete type of the client.
+#[derive(Debug, Clone)]
+pub enum OracleChoice {
+    Default(ProviderOracle<Arc<Provider<RpcProvider>>>),
+    FeeHistory(FeeHistoryOracle),
+}
+
+#[async_trait]
+impl GasOracle for OracleChoice {
+    async fn fetch(&self) -> Result<U256, GasOracleError> {
+        match self {
+            OracleChoice::Default(oracle) => oracle.fetch().await,
+            OracleChoice::FeeHistory(oracle) => oracle.fetch().await,
+        }
+    }
+
+    async fn estimate_eip1559_fees(&self) -> Result<(U256, U256), GasOracleError> {
+        match self {
+            OracleChoice::Default(oracle) => oracle.estimate_eip1559_fees().await,
+            OracleChoice::FeeHistory(oracle) => oracle.estimate_eip1559_fees().await,
+        }
+    }
+}
+
+/// Number of historical blocks sampled by [`FeeHistoryOracle`] when picking a
+/// priority fee. Wide enough to smooth over a single congested block.
+const FEE_HISTORY_BLOCKS: u64 = 20;
+
+/// A [`GasOracle`] that sources `maxFeePerGas`/`maxPriorityFeePerGas` from
+/// `eth_feeHistory` rather than the node's `eth_maxPriorityFeePerGas` guess.
+/// The priority fee is the median, across the sampled blocks, of the reward
+/// at the caller's chosen percentile; the max fee is `2 * latestBaseFee +
+/// priorityFee`, the same heuristic most wallets use to survive a few blocks
+/// of base fee growth.
+#[derive(Debug, Clone)]
+pub struct FeeHistoryOracle {
+    provider: Arc<Provider<RpcProvider>>,
+    priority_fee_percentile: f64,
+}
+
+impl FeeHistoryOracle {
+    pub fn new(provider: Arc<Provider<RpcProvider>>, priority_fee_percentile: f64) -> Self {
+        Self {
+            provider,
+            priority_fee_percentile,
+        }
+    }
+}
+
+#[async_trait]
+impl GasOracle for FeeHistoryOracle {
+    async fn fetch(&self) -> Result<U256, GasOracleError> {
+        let (max_fee, _) = self.estimate_eip1559_fees().await?;
+        Ok(max_fee)
+    }
+
+    async fn estimate_eip1559_fees(&self) -> Result<(U256, U256), GasOracleError> {
+        let history = self
+            .provider
+            .fee_history(
+                FEE_HISTORY_BLOCKS,
+                BlockNumber::Latest,
+                &[self.priority_fee_percentile],
+            )
+            .await
+            .map_err(|err| GasOracleError::ProviderError(Box::new(err)))?;
+
+        let base_fee = *history.base_fee_per_gas.last().unwrap_or(&U256::zero());
+        let rewards: Vec<U256> = history
+            .reward
+            .iter()
+            .filter_map(|block_rewards| block_rewards.first().copied())
+            .collect();
+
+        Ok(fees_from_history(base_fee, rewards))
+    }
+}
+
+/// The pure part of [`FeeHistoryOracle::estimate_eip1559_fees`]: the priority
+/// fee is the median of the per-block rewards at the caller's chosen
+/// percentile, and the max fee is `2 * latestBaseFee + priorityFee`, the same
+/// heuristic most wallets use to survive a few blocks of base fee growth.
+/// Split out from the `eth_feeHistory` call so the math can be unit tested
+/// without a live provider.
+fn fees_from_history(base_fee: U256, mut rewards: Vec<U256>) -> (U256, U256) {
+    rewards.sort();
+    let priority_fee = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+    let max_fee = base_fee * 2 + priority_fee;
+    (max_fee, priority_fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fees_from_history_takes_the_median_reward() {
+        let rewards = vec![U256::from(3), U256::from(1), U256::from(2)];
+        let (max_fee, priority_fee) = fees_from_history(U256::from(100), rewards);
+        assert_eq!(priority_fee, U256::from(2));
+        assert_eq!(max_fee, U256::from(202));
+    }
+
+    #[test]
+    fn fees_from_history_defaults_to_zero_priority_fee_with_no_rewards() {
+        let (max_fee, priority_fee) = fees_from_history(U256::from(100), vec![]);
+        assert_eq!(priority_fee, U256::zero());
+        assert_eq!(max_fee, U256::from(200));
+    }
+}
@@ -0,0 +1,93 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use ethers::providers::{
+    Http, HttpRateLimitRetryPolicy, Provider, Quorum, QuorumProvider, RetryClient, WeightedProvider,
+};
+use eyre::{bail, Context, Result};
+
+/// JSON-RPC transport used throughout the binary: one or more HTTP endpoints,
+/// each wrapped in a retrying client, combined behind a quorum so a single
+/// flaky or rate-limited node can't abort an in-flight activation.
+pub type RpcProvider = QuorumProvider<RetryClient<Http>>;
+
+/// Builds a [`Provider<RpcProvider>`] from one or more RPC endpoints. Each
+/// endpoint is wrapped in a [`RetryClient`] using an exponential backoff
+/// policy tuned for rate limiting (`429`s and the like), and the resulting
+/// transports are combined into a [`QuorumProvider`] that requires `quorum`
+/// of them to agree before a response is accepted. This keeps both the
+/// spoofed `eth_call` fee estimation and the final transaction submission
+/// resilient to a transient failure on any single endpoint.
+pub fn new_provider(
+    endpoints: &[String],
+    max_retries: u32,
+    initial_backoff_ms: u64,
+    quorum: Quorum,
+) -> Result<Provider<RpcProvider>> {
+    if endpoints.is_empty() {
+        bail!("at least one --endpoint is required");
+    }
+
+    let weighted = endpoints
+        .iter()
+        .map(|url| {
+            let http = Http::from_str(url).wrap_err("failed to init http transport")?;
+            let retry = RetryClient::new(
+                http,
+                Box::new(HttpRateLimitRetryPolicy),
+                max_retries,
+                initial_backoff_ms,
+            );
+            Ok::<_, eyre::ErrReport>(WeightedProvider::new(retry))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let quorum = QuorumProvider::builder()
+        .add_providers(weighted)
+        .quorum(quorum)
+        .build();
+
+    let mut provider = Provider::new(quorum);
+    provider.set_interval(Duration::from_millis(250));
+    Ok(provider)
+}
+
+/// Parses the `--quorum` flag: `all` requires every endpoint to agree,
+/// `majority` requires more than half, and any other value is parsed as a
+/// minimum number of agreeing endpoints (which must be at least 1 — a
+/// `Quorum::ProviderCount(0)` would accept the very first response and defeat
+/// the point of configuring a quorum at all).
+pub fn parse_quorum(s: &str) -> std::result::Result<Quorum, String> {
+    match s {
+        "all" => Ok(Quorum::All),
+        "majority" => Ok(Quorum::Majority),
+        n => match n.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!(
+                "invalid --quorum value: {n} (expected all, majority, or a number >= 1)"
+            )),
+            Ok(min) => Ok(Quorum::ProviderCount(min)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_quorum_accepts_all_and_majority() {
+        assert!(matches!(parse_quorum("all"), Ok(Quorum::All)));
+        assert!(matches!(parse_quorum("majority"), Ok(Quorum::Majority)));
+    }
+
+    #[test]
+    fn parse_quorum_accepts_a_minimum_count() {
+        assert!(matches!(parse_quorum("3"), Ok(Quorum::ProviderCount(3))));
+    }
+
+    #[test]
+    fn parse_quorum_rejects_zero_and_garbage() {
+        assert!(parse_quorum("0").is_err());
+        assert!(parse_quorum("not-a-number").is_err());
+    }
+}
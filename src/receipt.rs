@@ -0,0 +1,81 @@
+use std::fs;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use eyre::{Context, Result};
+use serde::Serialize;
+
+use crate::batch::{ActivationOutcome, ActivationReport};
+use crate::middleware::ActivationClient;
+
+/// A machine-readable record of a single activation, suitable for automated
+/// pipelines that activate programs on behalf of users.
+#[derive(Debug, Serialize)]
+pub struct ActivationReceipt {
+    pub address: H160,
+    pub codehash: H256,
+    pub estimated_fee: U256,
+    pub bumped_fee: U256,
+    pub version: u16,
+    pub block_number: Option<U64>,
+    pub gas_used: Option<U256>,
+    pub tx_hash: H256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Writes one JSON receipt per successfully activated program to `path`.
+/// Each receipt is signed by the same wallet that sent the activation: the
+/// Keccak-256 hash of the unsigned, canonically-encoded receipt is signed
+/// and the recoverable signature appended, so a downstream service can
+/// verify which operator performed the activation without trusting the
+/// transport.
+pub async fn write_receipts(
+    path: &str,
+    reports: &[ActivationReport],
+    client: &Arc<ActivationClient>,
+) -> Result<()> {
+    let mut receipts = Vec::new();
+    for report in reports {
+        let ActivationOutcome::Activated {
+            tx_hash,
+            codehash,
+            version,
+            block_number,
+            gas_used,
+        } = &report.outcome
+        else {
+            continue;
+        };
+
+        let mut receipt = ActivationReceipt {
+            address: report.address,
+            codehash: *codehash,
+            estimated_fee: report.estimated_fee.unwrap_or_default(),
+            bumped_fee: report.bumped_fee.unwrap_or_default(),
+            version: *version,
+            block_number: *block_number,
+            gas_used: *gas_used,
+            tx_hash: *tx_hash,
+            signature: None,
+        };
+
+        let unsigned = serde_json::to_vec(&receipt).wrap_err("failed to encode receipt")?;
+        let hash = keccak256(unsigned);
+        let signature = client
+            .inner()
+            .inner()
+            .signer()
+            .sign_message(hash)
+            .await
+            .map_err(|err| eyre::eyre!("failed to sign receipt: {err}"))?;
+        receipt.signature = Some(signature.to_string());
+
+        receipts.push(receipt);
+    }
+
+    let json = serde_json::to_string_pretty(&receipts).wrap_err("failed to encode receipts")?;
+    fs::write(path, json).wrap_err_with(|| format!("failed to write receipts to {path}"))?;
+    Ok(())
+}
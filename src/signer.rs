@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::signers::{HDPath, Ledger, LedgerError, LocalWallet, Signer as EthersSigner, WalletError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+use eyre::{eyre, Context, Result};
+
+/// The three ways a caller can authorize an activation transaction.
+///
+/// Raw private keys are convenient for local testing but should not be used
+/// to hold production funds, so we also support an encrypted JSON keystore
+/// and a Ledger hardware wallet. All three implement [`EthersSigner`] so the
+/// rest of the codebase can treat them identically once constructed.
+///
+/// Not `Clone`: the `Ledger` variant wraps a live HID session, which isn't
+/// cloneable, and nothing downstream needs to clone a `SignerBackend` once
+/// built.
+#[derive(Debug)]
+pub enum SignerBackend {
+    Local(LocalWallet),
+    Keystore(LocalWallet),
+    Ledger(Ledger),
+}
+
+impl SignerBackend {
+    /// Builds a [`SignerBackend`] from the CLI configuration. Exactly one of
+    /// `private_key`, `keystore`, or `ledger` is expected to be set by the
+    /// caller; precedence is ledger, then keystore, then raw private key.
+    pub async fn from_config(
+        private_key: &Option<String>,
+        keystore: &Option<String>,
+        ledger: bool,
+        hd_path: &str,
+        chain_id: u64,
+    ) -> Result<Self> {
+        if ledger {
+            let path = HDPath::Other(hd_path.to_string());
+            let wallet = Ledger::new(path, chain_id)
+                .await
+                .wrap_err("failed to connect to Ledger device")?;
+            return Ok(SignerBackend::Ledger(wallet));
+        }
+        if let Some(path) = keystore {
+            let password = rpassword::prompt_password("Keystore password: ")
+                .wrap_err("failed to read keystore password")?;
+            let wallet = LocalWallet::decrypt_keystore(path, password)
+                .wrap_err("failed to decrypt keystore")?;
+            return Ok(SignerBackend::Keystore(wallet.with_chain_id(chain_id)));
+        }
+        let key = private_key
+            .as_ref()
+            .ok_or_else(|| eyre!("one of --private-key, --keystore, or --ledger is required"))?;
+        let wallet = LocalWallet::from_str(key).wrap_err("failed to parse private key")?;
+        Ok(SignerBackend::Local(wallet.with_chain_id(chain_id)))
+    }
+}
+
+/// [`EthersSigner::Error`] for [`SignerBackend`]. `eyre::Report` (used
+/// elsewhere in this crate for application errors) doesn't implement
+/// `std::error::Error`, which `Signer::Error` requires, so the two concrete
+/// backend error types are wrapped here instead.
+#[derive(Debug, thiserror::Error)]
+pub enum SignerBackendError {
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
+
+#[async_trait]
+impl EthersSigner for SignerBackend {
+    type Error = SignerBackendError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            SignerBackend::Local(w) | SignerBackend::Keystore(w) => {
+                Ok(w.sign_message(message).await?)
+            }
+            SignerBackend::Ledger(l) => Ok(l.sign_message(message).await?),
+        }
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            SignerBackend::Local(w) | SignerBackend::Keystore(w) => {
+                Ok(w.sign_transaction(message).await?)
+            }
+            SignerBackend::Ledger(l) => Ok(l.sign_transaction(message).await?),
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            SignerBackend::Local(w) | SignerBackend::Keystore(w) => {
+                Ok(w.sign_typed_data(payload).await?)
+            }
+            SignerBackend::Ledger(l) => Ok(l.sign_typed_data(payload).await?),
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            SignerBackend::Local(w) | SignerBackend::Keystore(w) => w.address(),
+            SignerBackend::Ledger(l) => l.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            SignerBackend::Local(w) | SignerBackend::Keystore(w) => w.chain_id(),
+            SignerBackend::Ledger(l) => l.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            SignerBackend::Local(w) => SignerBackend::Local(w.with_chain_id(chain_id)),
+            SignerBackend::Keystore(w) => SignerBackend::Keystore(w.with_chain_id(chain_id)),
+            SignerBackend::Ledger(l) => SignerBackend::Ledger(l.with_chain_id(chain_id)),
+        }
+    }
+}
+
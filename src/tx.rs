@@ -0,0 +1,57 @@
+use ethers::prelude::*;
+use ethers::providers::Provider;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use eyre::{eyre, Result};
+
+use crate::provider::RpcProvider;
+
+/// Builds either an EIP-1559 or a legacy transaction to `to`, depending on
+/// `eip1559_supported`. Some Arbitrum Orbit chains and test networks reject
+/// type-2 transactions outright, so callers probe for EIP-1559 support with
+/// [`supports_eip1559`] once per run and pass the result in here rather than
+/// have every `build_tx` call re-probe it.
+pub fn build_tx(
+    legacy: bool,
+    eip1559_supported: bool,
+    from: Option<H160>,
+    to: H160,
+    value: U256,
+    data: Vec<u8>,
+    chain_id: u64,
+) -> TypedTransaction {
+    if legacy || !eip1559_supported {
+        let mut tx = TransactionRequest::new()
+            .to(to)
+            .value(value)
+            .data(data)
+            .chain_id(chain_id);
+        if let Some(from) = from {
+            tx = tx.from(from);
+        }
+        return TypedTransaction::Legacy(tx);
+    }
+
+    let mut tx = Eip1559TransactionRequest::new()
+        .to(to)
+        .value(value)
+        .data(data)
+        .chain_id(chain_id);
+    if let Some(from) = from {
+        tx = tx.from(from);
+    }
+    TypedTransaction::Eip1559(tx)
+}
+
+/// Detects EIP-1559 support by checking whether the latest block reports a
+/// `baseFeePerGas`, which only fee-market chains include. This is a property
+/// of the chain, not of any one transaction, so callers should probe it once
+/// per run (e.g. in [`batch_activate`](crate::batch::batch_activate)) and
+/// reuse the result across every [`build_tx`] call, rather than re-probing
+/// per transaction or per address.
+pub async fn supports_eip1559(provider: &Provider<RpcProvider>) -> Result<bool> {
+    let block = provider
+        .get_block(BlockNumber::Latest)
+        .await?
+        .ok_or_else(|| eyre!("failed to fetch latest block"))?;
+    Ok(block.base_fee_per_gas.is_some())
+}